@@ -1,4 +1,8 @@
-use drm::control::Device;
+use std::collections::VecDeque;
+use std::os::unix::io::AsRawFd;
+
+use drm::control::{Device, PageFlipFlags};
+use drm::Device as BasicDevice;
 use gbm::{AsRaw, BufferObjectFlags};
 use parking_lot::Mutex;
 use winit::{
@@ -27,51 +31,449 @@ macro_rules! pf_to_fmt {
     };
 }
 
+/// Parses the kernel's `IN_FORMATS` property blob (`struct
+/// drm_format_modifier_blob` in `drm_mode.h`) and returns the modifiers a
+/// plane advertises support for, for the given fourcc `format`. Returns an
+/// empty `Vec` on any malformed or unrecognised blob, which callers treat
+/// the same as "no modifier support".
+fn modifiers_for_format(blob: &[u8], format: gbm::Format) -> Vec<gbm::Modifier> {
+    fn read_u32(blob: &[u8], offset: usize) -> Option<u32> {
+        blob.get(offset..offset + 4).map(|b| u32::from_ne_bytes(b.try_into().unwrap()))
+    }
+
+    // Header: u32 version, u32 flags, then the four counts/offsets below.
+    let count_formats = match read_u32(blob, 8) {
+        Some(v) => v as usize,
+        None => return Vec::new(),
+    };
+    let formats_offset = match read_u32(blob, 12) {
+        Some(v) => v as usize,
+        None => return Vec::new(),
+    };
+    let count_modifiers = match read_u32(blob, 16) {
+        Some(v) => v as usize,
+        None => return Vec::new(),
+    };
+    let modifiers_offset = match read_u32(blob, 20) {
+        Some(v) => v as usize,
+        None => return Vec::new(),
+    };
+
+    let fourcc = format as u32;
+    let format_index = (0..count_formats).find(|i| {
+        read_u32(blob, formats_offset + i * 4) == Some(fourcc)
+    });
+    let format_index = match format_index {
+        Some(i) => i as u64,
+        None => return Vec::new(),
+    };
+
+    // Each `drm_format_modifier` entry is 24 bytes: a u64 format bitmask
+    // (relative to `offset`), a u32 offset, a u8 + 3 bytes of padding, and
+    // a u64 modifier.
+    const ENTRY_LEN: usize = 24;
+    let mut modifiers = Vec::new();
+    for i in 0..count_modifiers {
+        let base = modifiers_offset + i * ENTRY_LEN;
+        let formats_mask = match blob.get(base..base + 8) {
+            Some(b) => u64::from_ne_bytes(b.try_into().unwrap()),
+            None => continue,
+        };
+        let entry_offset = match read_u32(blob, base + 8) {
+            Some(v) => v as u64,
+            None => continue,
+        };
+        if format_index < entry_offset || format_index - entry_offset >= 64 {
+            continue;
+        }
+        if formats_mask & (1 << (format_index - entry_offset)) == 0 {
+            continue;
+        }
+        if let Some(modifier) = blob.get(base + 16..base + 24) {
+            modifiers.push(gbm::Modifier::from(u64::from_ne_bytes(modifier.try_into().unwrap())));
+        }
+    }
+    modifiers
+}
+
+/// Looks up the `IN_FORMATS` blob on a plane that can drive `crtc` and
+/// returns the modifiers it supports for `format`. Returns an empty list
+/// (meaning "fall back to linear") if no candidate plane, property, or blob
+/// can be found, which happens on drivers that never exposed
+/// `DRM_CAP_ADDFB2_MODIFIERS` in the first place.
+fn query_modifiers(
+    device: &gbm::Device<crate::platform::unix::Card>,
+    crtc: drm::control::crtc::Handle,
+    format: gbm::Format,
+) -> Vec<gbm::Modifier> {
+    if device.get_driver_capability(drm::DriverCapability::AddFb2Modifiers).unwrap_or(0) == 0 {
+        return Vec::new();
+    }
+    // `plane::Info::possible_crtcs` is a bitmask over the crtc list's
+    // order, not the plane's live assignment, so it's what we need here:
+    // this runs in `new_raw_context` before the first modeset, while every
+    // plane is still unassigned.
+    let resources = match device.resource_handles() {
+        Ok(resources) => resources,
+        Err(_) => return Vec::new(),
+    };
+    let crtc_index = match resources.crtcs().iter().position(|&c| c == crtc) {
+        Some(index) => index,
+        None => return Vec::new(),
+    };
+    let planes = match device.plane_handles() {
+        Ok(planes) => planes,
+        Err(_) => return Vec::new(),
+    };
+    for plane in planes {
+        let info = match device.get_plane(plane) {
+            Ok(info) => info,
+            Err(_) => continue,
+        };
+        if info.possible_crtcs() & (1 << crtc_index) == 0 {
+            continue;
+        }
+        let props = match device.get_properties(plane) {
+            Ok(props) => props,
+            Err(_) => continue,
+        };
+        for (prop, value) in props.iter() {
+            let prop_info = match device.get_property(*prop) {
+                Ok(info) => info,
+                Err(_) => continue,
+            };
+            if prop_info.name().to_bytes() != b"IN_FORMATS" {
+                continue;
+            }
+            if let Ok(blob) = device.get_property_blob(*value) {
+                return modifiers_for_format(&blob, format);
+            }
+        }
+    }
+    Vec::new()
+}
+
+/// Shorthand for the handle winit hands out for a GBM device: a static
+/// mutex guarding the `Result` from opening it.
+type GbmDeviceMutex =
+    &'static parking_lot::Mutex<AssertSync<Result<gbm::Device<crate::platform::unix::Card>, std::io::Error>>>;
+
+/// Userdata attached to every scanout-side buffer object so its
+/// framebuffer is created once and reused for as long as GBM keeps
+/// recycling that same underlying BO, instead of being added and torn
+/// down on every single swap. Dropping this (which only happens when GBM
+/// actually frees the BO, not when our `gbm::BufferObject` wrapper is
+/// merely released back to the surface's pool) destroys the framebuffer.
+struct FbUserData {
+    fb: drm::control::framebuffer::Handle,
+    device: GbmDeviceMutex,
+}
+
+impl std::fmt::Debug for FbUserData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FbUserData").field("fb", &self.fb).finish()
+    }
+}
+
+impl Drop for FbUserData {
+    fn drop(&mut self) {
+        let guard = self.device.lock();
+        if let Ok(device) = guard.as_ref() {
+            let _ = device.destroy_framebuffer(self.fb);
+        }
+    }
+}
+
+/// A framebuffer that is either currently scanned out or still waiting on
+/// the kernel to deliver its flip-completion event. It must not be
+/// released back to the surface's pool until the crtc has moved two
+/// generations past it; its actual framebuffer lives in the buffer
+/// object's userdata (see [`FbUserData`]) and outlives this entry.
+#[derive(Debug)]
+struct FlightBuffer {
+    /// The buffer actually programmed onto the crtc, on the scanout
+    /// device.
+    scanout_bo: gbm::BufferObject<FbUserData>,
+    /// When rendering happens on a separate render node, the buffer that
+    /// was rendered into, kept alive until `scanout_bo` (imported or
+    /// blitted from it) is itself retired. Its own userdata/framebuffer,
+    /// if any, is never used directly.
+    render_bo: Option<gbm::BufferObject<FbUserData>>,
+}
+
+/// A single plane of a buffer exported via
+/// [`Context::export_front_buffer_as_dmabuf`].
+#[derive(Debug)]
+pub struct DmabufPlane {
+    pub fd: std::os::unix::io::OwnedFd,
+    pub stride: u32,
+    pub offset: u32,
+}
+
+/// A scanned-out buffer exported as a dmabuf, one entry in `planes` per
+/// plane of `format`/`modifier`. See
+/// [`Context::export_front_buffer_as_dmabuf`].
+#[derive(Debug)]
+pub struct DmabufDescriptor {
+    pub format: gbm::Format,
+    pub modifier: gbm::Modifier,
+    pub planes: Vec<DmabufPlane>,
+}
+
 #[derive(Debug)]
 pub struct CtxLock {
-    device: &'static parking_lot::Mutex<
-        AssertSync<Result<gbm::Device<crate::platform::unix::Card>, std::io::Error>>,
-    >,
-    surface: Option<gbm::Surface<()>>,
-    previous_bo: Option<gbm::BufferObject<()>>,
-    previous_fb: Option<drm::control::framebuffer::Handle>,
+    /// The device that owns the crtc/connector and is actually scanned
+    /// out to.
+    device: GbmDeviceMutex,
+    /// The device rendering is performed on, when it differs from
+    /// `device` (a discrete render GPU feeding an integrated display
+    /// GPU). `None` means rendering and scanout share `device`, the
+    /// common case, and no cross-GPU import is needed.
+    render_device: Option<GbmDeviceMutex>,
+    surface: Option<gbm::Surface<FbUserData>>,
+    /// Buffers that are either the current scanout front buffer or still
+    /// pending a flip. Only the oldest entry may be retired, and only once
+    /// the crtc has moved on to a newer one: a later flip's completion
+    /// event has been observed on the DRM fd or, on the legacy path, a
+    /// blocking `set_crtc` to a newer buffer has returned.
+    in_flight: VecDeque<FlightBuffer>,
+    /// Set once the initial (blocking) `set_crtc` modeset has been done.
+    /// Subsequent frames only need to flip the framebuffer. `set_mode`
+    /// and `resize` clear this to force a blocking `set_crtc` for the new
+    /// mode on the next `swap_buffers`, since a page-flip cannot itself
+    /// change the mode.
+    crtc_initialized: bool,
+    /// Set when a `page_flip` has been submitted for this crtc and no
+    /// matching completion event has been drained yet. `swap_buffers`
+    /// waits on this before submitting another flip, since the kernel
+    /// rejects a second flip on the same crtc with `EBUSY` while one is
+    /// still outstanding.
+    flip_pending: bool,
+    /// The mode currently programmed (or, once `crtc_initialized` is
+    /// cleared, pending) on the crtc.
+    mode: drm::control::Mode,
+    /// The hardware cursor plane's buffer object, cached across frames so
+    /// `set_cursor` only has to re-upload pixels when the image actually
+    /// changes; `move_cursor` never touches this.
+    cursor: Option<CursorState>,
+}
+
+#[derive(Debug)]
+struct CursorState {
+    bo: gbm::BufferObject<()>,
+    width: u32,
+    height: u32,
+    image: Vec<u8>,
 }
 
 #[derive(Debug)]
 pub struct Context {
-    display: EglContext,
+    /// The EGL context and, for windowed contexts, its bound window
+    /// surface. Behind a lock because `resize` tears this down and
+    /// rebuilds it at the new dimensions in place.
+    display: parking_lot::Mutex<EglContext>,
     ctx_lock: parking_lot::Mutex<CtxLock>,
     depth: u32,
     bpp: u32,
+    /// The pixel format requirements this context was created with,
+    /// replayed by `resize` to rebuild `display` at a new size.
+    pf_reqs: PixelFormatRequirements,
+    /// The GL attributes this context was created with, sharing target
+    /// stripped (stored `()` in its place). `resize` rebuilds `display`
+    /// sharing with the context being replaced instead.
+    gl_attr: GlAttributes<()>,
     connector: drm::control::connector::Handle,
+    /// Every mode `connector` advertises, used to validate `set_mode` and
+    /// `resize` requests. Empty for surfaceless (headless) contexts.
+    modes: Vec<drm::control::Mode>,
     crtc: drm::control::crtc::Info,
-    mode: drm::control::Mode,
+    /// Whether the driver advertises atomic/page-flip support
+    /// (`DRM_CAP_ASYNC_PAGE_FLIP`). When `false` every `swap_buffers` falls
+    /// back to the old blocking `set_crtc` path.
+    supports_page_flip: bool,
+    /// The format modifier negotiated with the display plane at surface
+    /// creation time, if any. `None` means the surface is linear and
+    /// framebuffers are added with the legacy `add_framebuffer` call.
+    modifier: Option<gbm::Modifier>,
 }
 
-impl std::ops::Deref for Context {
-    type Target = EglContext;
+/// Returns whether the driver backing `device` advertises
+/// `DRM_CAP_ASYNC_PAGE_FLIP`/atomic page-flip support. Contexts on
+/// drivers that report no support always fall back to the legacy
+/// blocking `set_crtc` modeset on every frame.
+fn supports_async_page_flip(device: &gbm::Device<crate::platform::unix::Card>) -> bool {
+    device
+        .get_driver_capability(drm::DriverCapability::AsyncPageFlip)
+        .map(|cap| cap != 0)
+        .unwrap_or(false)
+}
 
-    fn deref(&self) -> &Self::Target {
-        &self.display
+/// Puts `device`'s underlying DRM fd in non-blocking mode. `receive_events`
+/// is a thin wrapper around `read(2)` on that fd, so without this,
+/// `drain_flip_events` (and therefore every `swap_buffers`) would block
+/// until the kernel delivers a flip-completion event, which is exactly the
+/// vblank stall async page-flipping is meant to avoid.
+fn set_nonblocking(device: &gbm::Device<crate::platform::unix::Card>) -> Result<(), CreationError> {
+    let fd = device.as_raw_fd();
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL, 0) };
+    if flags < 0 {
+        return Err(CreationError::OsError(std::io::Error::last_os_error().to_string()));
+    }
+    let ret = unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) };
+    if ret < 0 {
+        return Err(CreationError::OsError(std::io::Error::last_os_error().to_string()));
     }
+    Ok(())
+}
+
+/// Imports a buffer rendered on a different GPU into `scanout_device` as a
+/// dmabuf-backed buffer object, following Smithay's `primary_gpu` import
+/// pattern. The returned buffer object is ready to hand to
+/// `add_framebuffer`/`add_planar_framebuffer` on `scanout_device`.
+fn import_into_scanout_device(
+    scanout_device: &gbm::Device<crate::platform::unix::Card>,
+    render_bo: &gbm::BufferObject<FbUserData>,
+    modifier: Option<gbm::Modifier>,
+) -> std::io::Result<gbm::BufferObject<FbUserData>> {
+    let plane_count = render_bo.plane_count()? as usize;
+    let mut fds = Vec::with_capacity(plane_count);
+    let mut strides = Vec::with_capacity(plane_count);
+    let mut offsets = Vec::with_capacity(plane_count);
+    for i in 0..plane_count {
+        fds.push(render_bo.fd_for_plane(i)?);
+        strides.push(render_bo.stride_for_plane(i)?);
+        offsets.push(render_bo.offset(i)?);
+    }
+    let modifier = modifier.unwrap_or(render_bo.modifier()?);
+    scanout_device.import_buffer_object_from_dmabuf(
+        render_bo.width()?,
+        render_bo.height()?,
+        render_bo.format()?,
+        modifier,
+        &fds,
+        &strides,
+        &offsets,
+        BufferObjectFlags::SCANOUT,
+    )
+}
+
+/// Issues the AddFB2 (or legacy AddFB) ioctl to back `bo` with a kernel
+/// framebuffer, without any userdata caching of its own; callers decide
+/// which buffer object's userdata the result should be remembered under.
+fn add_framebuffer_for(
+    device: &gbm::Device<crate::platform::unix::Card>,
+    bo: &gbm::BufferObject<FbUserData>,
+    modifier: Option<gbm::Modifier>,
+    depth: u32,
+    bpp: u32,
+) -> Result<drm::control::framebuffer::Handle, ContextError> {
+    if modifier.is_some() {
+        // The buffer object carries its own per-plane stride/offset/
+        // modifier, so the planar AddFB2 path needs nothing from us
+        // beyond the buffer itself.
+        device
+            .add_planar_framebuffer(bo, drm::control::FbCmd2Flags::MODIFIERS)
+            .or_else(|e| {
+                Err(ContextError::OsError(format!("Error adding planar framebuffer: {}", e)))
+            })
+    } else {
+        device
+            .add_framebuffer(bo, depth, bpp)
+            .or_else(|e| Err(ContextError::OsError(format!("Error adding framebuffer: {}", e))))
+    }
+}
+
+/// Returns the framebuffer backing `bo`, creating it (and attaching it as
+/// the buffer object's userdata) the first time this particular buffer
+/// object is seen. GBM keeps a buffer object's userdata attached across
+/// `gbm_surface` release/re-acquire cycles, so once a swapchain has
+/// cycled through its buffers once, this stops adding and destroying a
+/// kernel framebuffer on every single frame.
+fn framebuffer_for_bo(
+    device_mtx: GbmDeviceMutex,
+    device: &gbm::Device<crate::platform::unix::Card>,
+    bo: &mut gbm::BufferObject<FbUserData>,
+    modifier: Option<gbm::Modifier>,
+    depth: u32,
+    bpp: u32,
+) -> Result<drm::control::framebuffer::Handle, ContextError> {
+    if let Some(data) = bo
+        .userdata()
+        .or_else(|e| Err(ContextError::OsError(format!("Error querying buffer userdata: {}", e))))?
+    {
+        return Ok(data.fb);
+    }
+    let fb = add_framebuffer_for(device, bo, modifier, depth, bpp)?;
+    bo.set_userdata(FbUserData { fb, device: device_mtx }).or_else(|e| {
+        Err(ContextError::OsError(format!("Error attaching framebuffer userdata: {}", e)))
+    })?;
+    Ok(fb)
+}
+
+/// Returns the framebuffer backing a scanout-side buffer object that was
+/// imported (via [`import_into_scanout_device`]) from a render-side buffer
+/// object on another GPU, creating it the first time this particular
+/// render-side buffer object is seen.
+///
+/// `import_into_scanout_device` allocates a brand new `gbm::BufferObject`
+/// wrapper on every call, even when the underlying dmabuf is one already
+/// imported, so (unlike [`framebuffer_for_bo`]) caching on the imported
+/// buffer's own userdata would miss every frame. GBM *does* keep recycling
+/// the same small set of render-side buffer objects across frames, so the
+/// cache lives on `render_bo` instead: `import_bo`'s userdata is left
+/// untouched, and its eventual drop does not destroy this framebuffer.
+fn framebuffer_for_imported_bo(
+    device_mtx: GbmDeviceMutex,
+    device: &gbm::Device<crate::platform::unix::Card>,
+    render_bo: &mut gbm::BufferObject<FbUserData>,
+    import_bo: &gbm::BufferObject<FbUserData>,
+    modifier: Option<gbm::Modifier>,
+    depth: u32,
+    bpp: u32,
+) -> Result<drm::control::framebuffer::Handle, ContextError> {
+    if let Some(data) = render_bo.userdata().or_else(|e| {
+        Err(ContextError::OsError(format!("Error querying buffer userdata: {}", e)))
+    })? {
+        return Ok(data.fb);
+    }
+    let fb = add_framebuffer_for(device, import_bo, modifier, depth, bpp)?;
+    render_bo.set_userdata(FbUserData { fb, device: device_mtx }).or_else(|e| {
+        Err(ContextError::OsError(format!("Error attaching framebuffer userdata: {}", e)))
+    })?;
+    Ok(fb)
 }
 
 impl Context {
     #[inline]
     pub fn new_headless<T>(
         el: &EventLoopWindowTarget<T>,
+        render_device_mtx: Option<GbmDeviceMutex>,
         pf_reqs: &PixelFormatRequirements,
         gl_attr: &GlAttributes<&Context>,
         _size: Option<winit::dpi::PhysicalSize<u32>>,
     ) -> Result<Self, CreationError> {
-        let gl_attr = gl_attr.clone().map_sharing(|c| &**c);
+        let stored_gl_attr = gl_attr.clone().map_sharing(|_| ());
+        // The share target, if any, is another live `Context` whose
+        // `display` is now behind a lock; hold it locked for exactly the
+        // `EglContext::new` call below, which is the only place the
+        // reference is used.
+        let share_guard = gl_attr.sharing.as_ref().map(|c| c.display.lock());
+        let gl_attr = gl_attr.clone().map_sharing(|_| &*share_guard.as_ref().unwrap());
         let display_ptr_mutex =
             el.gbm_device().ok_or(CreationError::NotSupported("GBM is not initialized".into()))?;
+        // A render device pointing at the very same mutex as the scanout
+        // device is just `None` in disguise: locking it again here would
+        // deadlock on this non-reentrant `parking_lot::Mutex`.
+        let render_device_mtx = render_device_mtx.filter(|m| !std::ptr::eq(*m, display_ptr_mutex));
         let display_ptr = display_ptr_mutex.lock();
-        let native_display = NativeDisplay::Gbm(Some(
-            display_ptr.as_ref().map_err(|e| CreationError::OsError(e.to_string()))?.as_raw()
-                as *const _,
-        ));
+        let render_ptr_mtx = render_device_mtx.unwrap_or(display_ptr_mutex);
+        let render_ptr = if render_device_mtx.is_some() { Some(render_ptr_mtx.lock()) } else { None };
+        let render_ref = render_ptr
+            .as_ref()
+            .map(|p| p.as_ref().map_err(|e| CreationError::OsError(e.to_string())))
+            .unwrap_or_else(|| display_ptr.as_ref().map_err(|e| CreationError::OsError(e.to_string())))?;
+        set_nonblocking(display_ptr.as_ref().map_err(|e| CreationError::OsError(e.to_string()))?)?;
+        let native_display = NativeDisplay::Gbm(Some(render_ref.as_raw() as *const _));
         let context = EglContext::new(
             pf_reqs,
             &gl_attr,
@@ -80,21 +482,27 @@ impl Context {
             |c, _| Ok(c[0]),
         )
         .and_then(|p| p.finish_surfaceless())?;
+        drop(share_guard);
         let context = Context {
-            display: context,
+            display: Mutex::new(context),
             ctx_lock: Mutex::new(CtxLock {
                 device: el
                     .gbm_device()
                     .ok_or(CreationError::NotSupported("GBM is not initialized".into()))?,
+                render_device: render_device_mtx,
                 surface: None,
-                previous_fb: None,
-                previous_bo: None,
+                in_flight: VecDeque::new(),
+                crtc_initialized: false,
+                flip_pending: false,
+                mode: el
+                    .gbm_mode()
+                    .ok_or(CreationError::NotSupported("GBM is not initialized".into()))?,
+                cursor: None,
             }),
             depth: pf_reqs.depth_bits.unwrap_or(0) as u32,
-            mode: el
-                .gbm_mode()
-                .ok_or(CreationError::NotSupported("GBM is not initialized".into()))?,
             bpp: pf_reqs.alpha_bits.unwrap_or(0) as u32 + pf_reqs.color_bits.unwrap_or(0) as u32,
+            pf_reqs: pf_reqs.clone(),
+            gl_attr: stored_gl_attr,
             crtc: el
                 .gbm_crtc()
                 .ok_or(CreationError::NotSupported("GBM is not initialized".into()))?
@@ -103,6 +511,12 @@ impl Context {
                 .gbm_connector()
                 .ok_or(CreationError::NotSupported("GBM is not initialized".into()))?
                 .handle(),
+            // Headless contexts have no connector to resize against.
+            modes: Vec::new(),
+            supports_page_flip: supports_async_page_flip(
+                display_ptr.as_ref().map_err(|e| CreationError::OsError(e.to_string()))?,
+            ),
+            modifier: None,
         };
         Ok(context)
     }
@@ -119,6 +533,7 @@ impl Context {
         let (width, height): (u32, u32) = size.into();
         let ctx = Self::new_raw_context(
             el.gbm_device().ok_or(CreationError::NotSupported("GBM is not initialized".into()))?,
+            None,
             width,
             height,
             el.gbm_crtc().ok_or(CreationError::OsError("No crtc found".to_string()))?,
@@ -130,11 +545,14 @@ impl Context {
         Ok((window, ctx))
     }
 
+    /// Creates a context, optionally rendering on a GPU other than the one
+    /// that owns `crt`/`con` (e.g. a laptop's discrete render node feeding
+    /// an integrated display GPU). When `render_device_mtx` is `None`,
+    /// rendering and scanout share `display_ptr_mtx` exactly as before.
     #[inline]
     pub fn new_raw_context(
-        display_ptr_mtx: &'static parking_lot::Mutex<
-            AssertSync<Result<gbm::Device<crate::platform::unix::Card>, std::io::Error>>,
-        >,
+        display_ptr_mtx: GbmDeviceMutex,
+        render_device_mtx: Option<GbmDeviceMutex>,
         width: u32,
         height: u32,
         crt: &drm::control::crtc::Info,
@@ -143,96 +561,240 @@ impl Context {
         pf_reqs: &PixelFormatRequirements,
         gl_attr: &GlAttributes<&Context>,
     ) -> Result<Self, CreationError> {
+        // A render device pointing at the very same mutex as the scanout
+        // device is just `None` in disguise: locking it again below would
+        // deadlock on this non-reentrant `parking_lot::Mutex`.
+        let render_device_mtx = render_device_mtx.filter(|m| !std::ptr::eq(*m, display_ptr_mtx));
         let display_ptr = display_ptr_mtx.lock();
-        let gl_attr = gl_attr.clone().map_sharing(|c| &**c);
+        let render_ptr_mtx = render_device_mtx.unwrap_or(display_ptr_mtx);
+        // Locking the same mutex twice on one thread would deadlock, so
+        // only take a second lock when the devices actually differ.
+        let render_ptr = if render_device_mtx.is_some() { Some(render_ptr_mtx.lock()) } else { None };
+        let render_ref = render_ptr
+            .as_ref()
+            .map(|p| p.as_ref().map_err(|e| CreationError::OsError(e.to_string())))
+            .unwrap_or_else(|| display_ptr.as_ref().map_err(|e| CreationError::OsError(e.to_string())))?;
+
+        let stored_gl_attr = gl_attr.clone().map_sharing(|_| ());
+        // The share target, if any, is another live `Context` whose
+        // `display` is now behind a lock; hold it locked for exactly the
+        // `EglContext::new` call below, which is the only place the
+        // reference is used.
+        let share_guard = gl_attr.sharing.as_ref().map(|c| c.display.lock());
+        let gl_attr = gl_attr.clone().map_sharing(|_| &*share_guard.as_ref().unwrap());
         let format = pf_to_fmt!(pf_reqs);
 
         let context = EglContext::new(
             pf_reqs,
             &gl_attr,
-            NativeDisplay::Gbm(Some(
-                display_ptr.as_ref().map_err(|e| CreationError::OsError(e.to_string()))?.as_raw()
-                    as ffi::EGLNativeDisplayType,
-            )),
+            NativeDisplay::Gbm(Some(render_ref.as_raw() as ffi::EGLNativeDisplayType)),
             EglSurfaceType::Window,
             |c, _| Ok(c[0]),
         )?;
+        drop(share_guard);
 
-        let surface: gbm::Surface<()> = display_ptr
-            .as_ref()
-            .map_err(|e| CreationError::OsError(e.to_string()))?
-            .create_surface(
-                width,
-                height,
-                format,
-                BufferObjectFlags::SCANOUT | BufferObjectFlags::RENDERING,
-            )
-            .map_err(|e| CreationError::OsError(e.to_string()))?;
+        let scanout_ref = display_ptr.as_ref().map_err(|e| CreationError::OsError(e.to_string()))?;
+        set_nonblocking(scanout_ref)?;
+        let modifiers = query_modifiers(scanout_ref, crt.handle(), format);
+        let (surface, modifier): (gbm::Surface<FbUserData>, Option<gbm::Modifier>) = if modifiers.is_empty()
+        {
+            let surface = render_ref
+                .create_surface(
+                    width,
+                    height,
+                    format,
+                    BufferObjectFlags::SCANOUT | BufferObjectFlags::RENDERING,
+                )
+                .map_err(|e| CreationError::OsError(e.to_string()))?;
+            (surface, None)
+        } else {
+            match render_ref.create_surface_with_modifiers(width, height, format, modifiers.clone())
+            {
+                Ok(surface) => {
+                    // The buffer objects this surface produces don't commit
+                    // to a single modifier up front; each BO carries the one
+                    // it actually landed on, which the planar AddFB2 path
+                    // reads from the BO itself. The negotiated set's first
+                    // entry is recorded as the context's modifier, which
+                    // `resize` and the cross-GPU import use.
+                    (surface, modifiers.into_iter().next())
+                }
+                Err(_) => {
+                    // Modifier negotiation failed (e.g. a stale/incompatible
+                    // modifier list); fall back to a plain linear surface.
+                    let surface = render_ref
+                        .create_surface(
+                            width,
+                            height,
+                            format,
+                            BufferObjectFlags::SCANOUT | BufferObjectFlags::RENDERING,
+                        )
+                        .map_err(|e| CreationError::OsError(e.to_string()))?;
+                    (surface, None)
+                }
+            }
+        };
 
         let display = context.finish(surface.as_raw() as ffi::EGLNativeWindowType)?;
+        let supports_page_flip = supports_async_page_flip(scanout_ref);
 
         let ctx = Context {
-            display,
-            mode,
+            display: Mutex::new(display),
             ctx_lock: Mutex::new(CtxLock {
                 device: display_ptr_mtx,
+                render_device: render_device_mtx,
                 surface: Some(surface),
-                previous_fb: None,
-                previous_bo: None,
+                in_flight: VecDeque::new(),
+                crtc_initialized: false,
+                flip_pending: false,
+                mode,
+                cursor: None,
             }),
+            supports_page_flip,
+            modifier,
             depth: pf_reqs.depth_bits.unwrap_or(0) as u32,
             bpp: pf_reqs.alpha_bits.unwrap_or(0) as u32 + pf_reqs.color_bits.unwrap_or(0) as u32,
+            pf_reqs: pf_reqs.clone(),
+            gl_attr: stored_gl_attr,
             crtc: crt.clone(),
             connector: con.handle(),
+            modes: con.modes().to_vec(),
         };
         Ok(ctx)
     }
 
     #[inline]
     pub unsafe fn make_not_current(&self) -> Result<(), ContextError> {
-        (**self).make_not_current()
+        self.display.lock().make_not_current()
     }
 
     #[inline]
     pub fn is_current(&self) -> bool {
-        (**self).is_current()
+        self.display.lock().is_current()
     }
 
     #[inline]
     pub fn get_api(&self) -> crate::Api {
-        (**self).get_api()
+        self.display.lock().get_api()
     }
 
     #[inline]
     pub unsafe fn raw_handle(&self) -> ffi::EGLContext {
-        (**self).raw_handle()
+        self.display.lock().raw_handle()
     }
 
     #[inline]
     pub unsafe fn get_egl_display(&self) -> Option<*const std::os::raw::c_void> {
-        Some((**self).get_egl_display())
+        Some(self.display.lock().get_egl_display())
     }
 
-    #[inline]
-    pub fn resize(&self, width: u32, height: u32) {
-        /*
-        match self {
-        Context::Windowed(_, surface) => surface.0.resize(width as i32, height as i32, 0, 0),
-        _ => unreachable!(),
+    /// Returns the mode in this context's connector's advertised list
+    /// matching `width`x`height`. When more than one mode matches
+    /// (different refresh rates), the first one listed is used.
+    fn mode_for_size(&self, width: u32, height: u32) -> Option<drm::control::Mode> {
+        self.modes.iter().copied().find(|mode| {
+            let (w, h) = mode.size();
+            u32::from(w) == width && u32::from(h) == height
+        })
+    }
+
+    /// Switches the crtc to `mode` on the next `swap_buffers`, without
+    /// touching the rendering surface. `mode` must be one advertised by
+    /// this context's connector; `resize` is the usual way to pick one by
+    /// size instead of by `drm::control::Mode` directly.
+    pub fn set_mode(&self, mode: drm::control::Mode) -> Result<(), ContextError> {
+        if !self.modes.contains(&mode) {
+            return Err(ContextError::OsError(
+                "Requested mode is not advertised by this context's connector".to_string(),
+            ));
         }
-        */
+        let mut lock = self.ctx_lock.lock();
+        lock.mode = mode;
+        // A page-flip cannot itself change the mode, so force a blocking
+        // `set_crtc` for this mode on the next frame.
+        lock.crtc_initialized = false;
+        Ok(())
+    }
+
+    /// Resizes the windowed surface to `width`x`height`: tears down the
+    /// current GBM surface and its bound EGL window surface and recreates
+    /// both at the new dimensions, sharing GL objects with the context
+    /// being replaced. The connector must actually advertise a mode at
+    /// this size, found via [`Context::mode_for_size`]; `swap_buffers`
+    /// picks up the resulting mode change (see [`Context::set_mode`]) and
+    /// issues a full `set_crtc` on its next call.
+    pub fn resize(&self, width: u32, height: u32) -> Result<(), ContextError> {
+        let mode = self.mode_for_size(width, height).ok_or_else(|| {
+            ContextError::OsError(format!(
+                "No mode advertised by this context's connector matches {}x{}",
+                width, height
+            ))
+        })?;
+
+        let mut lock = self.ctx_lock.lock();
+        let d_lock = lock.device.lock();
+        let device =
+            d_lock.as_ref().or(Err(ContextError::OsError("GBM is uninitialized".to_string())))?;
+        let render_lock = lock.render_device.map(|m| m.lock());
+        let render_ref = render_lock
+            .as_ref()
+            .map(|p| p.as_ref().or(Err(ContextError::OsError("GBM is uninitialized".to_string()))))
+            .unwrap_or(Ok(device))?;
+
+        let format = pf_to_fmt!(self.pf_reqs);
+        let surface = if let Some(modifier) = self.modifier {
+            render_ref
+                .create_surface_with_modifiers(width, height, format, vec![modifier])
+                .or_else(|e| Err(ContextError::OsError(format!("Error creating surface: {}", e))))?
+        } else {
+            render_ref
+                .create_surface(
+                    width,
+                    height,
+                    format,
+                    BufferObjectFlags::SCANOUT | BufferObjectFlags::RENDERING,
+                )
+                .or_else(|e| Err(ContextError::OsError(format!("Error creating surface: {}", e))))?
+        };
+
+        let mut display_lock = self.display.lock();
+        let base_gl_attr = self.gl_attr.clone().map_sharing(|_| &*display_lock);
+        let gl_attr = GlAttributes { sharing: Some(&*display_lock), ..base_gl_attr };
+        let new_display = EglContext::new(
+            &self.pf_reqs,
+            &gl_attr,
+            NativeDisplay::Gbm(Some(render_ref.as_raw() as ffi::EGLNativeDisplayType)),
+            EglSurfaceType::Window,
+            |c, _| Ok(c[0]),
+        )
+        .and_then(|ctx| ctx.finish(surface.as_raw() as ffi::EGLNativeWindowType))
+        .map_err(|e| ContextError::OsError(format!("Error recreating EGL surface: {:?}", e)))?;
+        *display_lock = new_display;
+        drop(display_lock);
+
+        // Drop the buffers still checked out from the old surface before
+        // replacing it: libgbm requires every locked buffer to be released
+        // back to its surface before that surface is destroyed.
+        lock.in_flight.clear();
+        lock.surface = Some(surface);
+        lock.mode = mode;
+        // A page-flip cannot itself change the mode, so force a blocking
+        // `set_crtc` for this mode on the next frame.
+        lock.crtc_initialized = false;
+        Ok(())
     }
 
     #[inline]
     pub fn get_proc_address(&self, addr: &str) -> *const core::ffi::c_void {
-        (**self).get_proc_address(addr)
+        self.display.lock().get_proc_address(addr)
     }
 
     #[inline]
     pub fn swap_buffers(&self) -> Result<(), ContextError> {
-        (**self).swap_buffers()?;
+        self.display.lock().swap_buffers()?;
         let mut lock = self.ctx_lock.lock();
-        let front_buffer = unsafe {
+        self.wait_for_pending_flip(&mut lock)?;
+        let mut render_buffer = unsafe {
             lock.surface
                 .as_ref()
                 .ok_or(ContextError::OsError("This context is surfaceless".to_string()))?
@@ -242,42 +804,306 @@ impl Context {
                 })?
         };
         let d_lock = lock.device.lock();
-        let fb = d_lock
-            .as_ref()
-            .or(Err(ContextError::OsError("GBM is uninitialized".to_string())))?
-            .add_framebuffer(&front_buffer, self.depth, self.bpp)
-            .or_else(|e| Err(ContextError::OsError(format!("Error adding framebuffer: {}", e))))?;
-        d_lock
-            .as_ref()
-            .or(Err(ContextError::OsError("GBM is uninitialized".to_string())))?
-            .set_crtc(self.crtc.handle(), Some(fb), (0, 0), &[self.connector], Some(self.mode))
-            .or_else(|e| Err(ContextError::OsError(format!("Error setting crtc: {}", e))))?;
-        if let Some(prev_fb) = lock.previous_fb {
-            d_lock
+        let device =
+            d_lock.as_ref().or(Err(ContextError::OsError("GBM is uninitialized".to_string())))?;
+
+        // When rendering happens on a separate render node, the buffer we
+        // just locked lives on that device and can't be scanned out
+        // directly: import it into the scanout device as a dmabuf-backed
+        // buffer object first.
+        //
+        // Known gap, deliberately left unimplemented: GPU pairs that can't
+        // share an importable format/modifier have no fallback here. Making
+        // that case work would mean standing up a short-lived EGL context
+        // bound to the scanout device, importing the render-side dmabuf as
+        // a GL texture, and blitting it into a scanout-device buffer object
+        // every frame — real work, not a stub, and out of scope for this
+        // change. Until someone does that, a failed import is reported as
+        // an error rather than silently producing a corrupt frame.
+        let is_split_gpu = lock
+            .render_device
+            .map_or(false, |render_device| !std::ptr::eq(render_device, lock.device));
+        let (mut scanout_bo, render_bo, fb) = if is_split_gpu {
+            let imported =
+                import_into_scanout_device(device, &render_buffer, self.modifier).or_else(|e| {
+                    Err(ContextError::OsError(format!(
+                        "Error importing cross-GPU render buffer into the scanout device: {}",
+                        e
+                    )))
+                })?;
+            let fb = framebuffer_for_imported_bo(
+                lock.device,
+                device,
+                &mut render_buffer,
+                &imported,
+                self.modifier,
+                self.depth,
+                self.bpp,
+            )?;
+            (imported, Some(render_buffer), fb)
+        } else {
+            let fb = framebuffer_for_bo(
+                lock.device,
+                device,
+                &mut render_buffer,
+                self.modifier,
+                self.depth,
+                self.bpp,
+            )?;
+            (render_buffer, None, fb)
+        };
+
+        if !lock.crtc_initialized || !self.supports_page_flip {
+            // Either this is the first frame since creation or since a mode
+            // change (`set_mode`/`resize`), or the driver cannot page-flip
+            // at all: fall back to the blocking legacy modeset.
+            // The crtc keeps this mode for every subsequent flip.
+            device
+                .set_crtc(self.crtc.handle(), Some(fb), (0, 0), &[self.connector], Some(lock.mode))
+                .or_else(|e| Err(ContextError::OsError(format!("Error setting crtc: {}", e))))?;
+            lock.crtc_initialized = true;
+        } else {
+            device
+                .page_flip(self.crtc.handle(), fb, PageFlipFlags::EVENT, None)
+                .or_else(|e| Err(ContextError::OsError(format!("Error page-flipping: {}", e))))?;
+            lock.flip_pending = true;
+        }
+        lock.in_flight.push_back(FlightBuffer { scanout_bo, render_bo });
+
+        drop(d_lock);
+        self.drain_flip_events(&mut lock)?;
+        Ok(())
+    }
+
+    /// Blocks until the crtc has confirmed any page-flip submitted by a
+    /// previous `swap_buffers` has actually completed. The legacy
+    /// `DRM_IOCTL_MODE_PAGE_FLIP` ioctl this wraps returns `EBUSY` if a
+    /// flip is still pending on the crtc, and since `swap_buffers` no
+    /// longer blocks on vblank itself, an unthrottled render loop would
+    /// otherwise submit a second flip before the first one's completion
+    /// event is drained and see that `EBUSY` surface as an `OsError`. This
+    /// restores normal frame pacing without reintroducing a vblank stall
+    /// on every single frame: it only blocks on the (rare, already
+    /// imminent) case where the previous flip genuinely hasn't completed
+    /// yet. See also [`Context::can_swap`] for a non-blocking version of
+    /// this check.
+    fn wait_for_pending_flip(&self, lock: &mut CtxLock) -> Result<(), ContextError> {
+        while lock.flip_pending {
+            let fd = {
+                let d_lock = lock.device.lock();
+                let device = d_lock
+                    .as_ref()
+                    .or(Err(ContextError::OsError("GBM is uninitialized".to_string())))?;
+                device.as_raw_fd()
+            };
+            let mut poll_fd = libc::pollfd { fd, events: libc::POLLIN, revents: 0 };
+            // SAFETY: `poll_fd` is a single, valid, stack-local `pollfd`.
+            let ret = unsafe { libc::poll(&mut poll_fd, 1, -1) };
+            if ret < 0 {
+                return Err(ContextError::OsError(std::io::Error::last_os_error().to_string()));
+            }
+            self.drain_flip_events(lock)?;
+        }
+        Ok(())
+    }
+
+    /// Returns whether `swap_buffers` can be called right now without
+    /// blocking on a previous page-flip's completion. Callers driving their
+    /// own frame pacing (e.g. skipping a frame instead of stalling) can
+    /// poll this instead of letting `swap_buffers` wait; see
+    /// [`Context::poll_events`] to advance it without swapping.
+    pub fn can_swap(&self) -> bool {
+        !self.ctx_lock.lock().flip_pending
+    }
+
+    /// Drains any flip-completion events already queued on the DRM fd and
+    /// retires the corresponding buffers. A buffer is only ever dropped
+    /// after the kernel has confirmed it is no longer scanned out, so we
+    /// never release a buffer that is still in use. Retiring a
+    /// [`FlightBuffer`] does not destroy its framebuffer: that lives in the
+    /// buffer object's userdata (see [`FbUserData`]) and is reused the next
+    /// time GBM hands this same buffer object back. This does not block:
+    /// the device's fd is put in non-blocking mode at creation time (see
+    /// [`set_nonblocking`]), so with no event queued `receive_events`
+    /// returns immediately, and with legacy `set_crtc` there are no flip
+    /// events to wait for at all, so the oldest in-flight buffer is retired
+    /// immediately once it is no longer the front buffer.
+    ///
+    /// The fd is shared by every `Context` scanning out through the same
+    /// `gbm::Device` (one per crtc, in a multi-monitor setup), so an event
+    /// for another context's crtc can show up here; only events carrying
+    /// this context's own crtc are treated as a retirement.
+    fn drain_flip_events(&self, lock: &mut CtxLock) -> Result<(), ContextError> {
+        if self.supports_page_flip {
+            let d_lock = lock.device.lock();
+            let device = d_lock
                 .as_ref()
-                .or(Err(ContextError::OsError("GBM is uninitialized".to_string())))?
-                .destroy_framebuffer(prev_fb)
+                .or(Err(ContextError::OsError("GBM is uninitialized".to_string())))?;
+            let events = match device.receive_events() {
+                Ok(events) => events,
+                // The fd is non-blocking; no event queued yet is the
+                // common case on every frame, not an error.
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(ContextError::OsError(format!("Error reading DRM events: {}", e))),
+            };
+            for event in events {
+                if let drm::control::Event::PageFlip(flip) = event {
+                    if flip.crtc == self.crtc.handle() {
+                        lock.flip_pending = false;
+                        if lock.in_flight.len() > 1 {
+                            lock.in_flight.pop_front();
+                        }
+                    }
+                }
+            }
+        } else {
+            // No events are ever generated for a blocking modeset; the
+            // crtc has already moved on to the newest buffer by the time
+            // `set_crtc` returns, so the previous one (if any) is free.
+            while lock.in_flight.len() > 1 {
+                lock.in_flight.pop_front();
+            }
+        }
+        Ok(())
+    }
+
+    /// Polls the DRM fd for flip-completion events without blocking,
+    /// retiring any buffers that have been fully released by the kernel.
+    /// Callers that want to know when another `swap_buffers` can be
+    /// issued without stalling on a pending flip can use this to drive
+    /// their own event loop integration (e.g. registering the fd with
+    /// `calloop`/`epoll`).
+    #[inline]
+    pub fn poll_events(&self) -> Result<(), ContextError> {
+        let mut lock = self.ctx_lock.lock();
+        self.drain_flip_events(&mut lock)
+    }
+
+    /// Exports the most recently swapped buffer (the newest one submitted
+    /// to the crtc, whose page-flip may still be pending) as a dmabuf, for
+    /// zero-copy sharing with screen capture, video encode, or another
+    /// GPU. Each returned plane's file descriptor is its own reference to
+    /// the underlying dmabuf, so it stays valid even after this `Context`
+    /// (and the GBM buffer object backing it) goes away; each fd is an
+    /// `OwnedFd` and is closed when the returned descriptor is dropped.
+    ///
+    /// Returns an error if this context is surfaceless or no frame has
+    /// been swapped yet.
+    pub fn export_front_buffer_as_dmabuf(&self) -> Result<DmabufDescriptor, ContextError> {
+        let lock = self.ctx_lock.lock();
+        if lock.surface.is_none() {
+            return Err(ContextError::OsError("This context is surfaceless".to_string()));
+        }
+        let front = lock
+            .in_flight
+            .back()
+            .ok_or(ContextError::OsError("No buffer has been swapped yet".to_string()))?;
+        let bo = &front.scanout_bo;
+
+        let plane_count = bo
+            .plane_count()
+            .or_else(|e| Err(ContextError::OsError(format!("Error querying plane count: {}", e))))?
+            as usize;
+
+        let mut planes = Vec::with_capacity(plane_count);
+        for i in 0..plane_count {
+            let fd = bo
+                .fd_for_plane(i)
+                .or_else(|e| Err(ContextError::OsError(format!("Error exporting plane: {}", e))))?;
+            let stride = bo.stride_for_plane(i).or_else(|e| {
+                Err(ContextError::OsError(format!("Error querying plane stride: {}", e)))
+            })?;
+            let offset = bo
+                .offset(i)
+                .or_else(|e| Err(ContextError::OsError(format!("Error querying plane offset: {}", e))))?;
+            planes.push(DmabufPlane { fd, stride, offset });
+        }
+
+        Ok(DmabufDescriptor {
+            format: bo
+                .format()
+                .or_else(|e| Err(ContextError::OsError(format!("Error querying format: {}", e))))?,
+            modifier: bo
+                .modifier()
+                .or_else(|e| Err(ContextError::OsError(format!("Error querying modifier: {}", e))))?,
+            planes,
+        })
+    }
+
+    /// Programs the hardware cursor plane with `image` (tightly packed
+    /// `ARGB8888`, `width * height` pixels) at `hotspot`. The cursor stays
+    /// on its own plane and moves independently of `swap_buffers`; the
+    /// underlying buffer object is cached in `CtxLock` and only
+    /// re-uploaded when the image actually changes.
+    pub fn set_cursor(
+        &self,
+        image: &[u8],
+        width: u32,
+        height: u32,
+        hotspot: (i32, i32),
+    ) -> Result<(), ContextError> {
+        let mut lock = self.ctx_lock.lock();
+        let d_lock = lock.device.lock();
+        let device =
+            d_lock.as_ref().or(Err(ContextError::OsError("GBM is uninitialized".to_string())))?;
+
+        let needs_upload = !matches!(
+            &lock.cursor,
+            Some(cursor) if cursor.width == width && cursor.height == height && cursor.image == image
+        );
+
+        if needs_upload {
+            let mut bo: gbm::BufferObject<()> = device
+                .create_buffer_object(
+                    width,
+                    height,
+                    gbm::Format::Argb8888,
+                    BufferObjectFlags::CURSOR | BufferObjectFlags::WRITE,
+                )
                 .or_else(|e| {
-                    Err(ContextError::OsError(format!("Error destroying framebuffer: {}", e)))
-                })?
+                    Err(ContextError::OsError(format!("Error creating cursor buffer: {}", e)))
+                })?;
+            bo.write(image).or_else(|e| {
+                Err(ContextError::OsError(format!("Error uploading cursor image: {}", e)))
+            })?;
+            lock.cursor = Some(CursorState { bo, width, height, image: image.to_vec() });
         }
-        lock.previous_fb = Some(fb);
-        lock.previous_bo = Some(front_buffer);
+
+        device
+            .set_cursor2(
+                self.crtc.handle(),
+                lock.cursor.as_ref().map(|cursor| &cursor.bo),
+                hotspot,
+            )
+            .or_else(|e| Err(ContextError::OsError(format!("Error setting cursor: {}", e))))?;
+        Ok(())
+    }
+
+    /// Moves the hardware cursor plane to `(x, y)`. This only issues the
+    /// cheap position-update ioctl; it never touches the cursor image.
+    pub fn move_cursor(&self, x: i32, y: i32) -> Result<(), ContextError> {
+        let lock = self.ctx_lock.lock();
+        let d_lock = lock.device.lock();
+        d_lock
+            .as_ref()
+            .or(Err(ContextError::OsError("GBM is uninitialized".to_string())))?
+            .move_cursor(self.crtc.handle(), (x, y))
+            .or_else(|e| Err(ContextError::OsError(format!("Error moving cursor: {}", e))))?;
         Ok(())
     }
 
     #[inline]
     pub fn swap_buffers_with_damage(&self, rects: &[Rect]) -> Result<(), ContextError> {
-        (**self).swap_buffers_with_damage(rects)
+        self.display.lock().swap_buffers_with_damage(rects)
     }
 
     #[inline]
     pub fn swap_buffers_with_damage_supported(&self) -> bool {
-        (**self).swap_buffers_with_damage_supported()
+        self.display.lock().swap_buffers_with_damage_supported()
     }
 
     #[inline]
     pub fn get_pixel_format(&self) -> PixelFormat {
-        (**self).get_pixel_format().clone()
+        self.display.lock().get_pixel_format().clone()
     }
 }
\ No newline at end of file